@@ -1,7 +1,15 @@
-mod tsp;
+use rand::SeedableRng;
+use tsp_solver::tsp;
 
 fn main() {
     let mut colony = tsp::Colony::new();
-    colony.run(1000, &mut tsp::Dummy, &tsp::Dummy);
+    let mut rng = rand::rngs::SmallRng::from_entropy();
+    colony.run(
+        1000,
+        &tsp::Dummy,
+        &tsp::Dummy,
+        &mut rng,
+        &tsp::Itinerary::default(),
+    );
     println!("{:?}", colony.best_path());
 }
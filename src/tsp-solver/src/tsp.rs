@@ -1,8 +1,15 @@
 //! A generic ant-colony simulation travelling salesman solver.
 
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 
-#[derive(Debug, Default, Clone)]
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Colony {
     // Edge pheromone levels
     edges: HashMap<EdgeKey, Edge>,
@@ -14,15 +21,71 @@ pub struct Colony {
     // A quality of "0" indicates that no path has ever been found.
     best_path: (f32, Vec<usize>),
 
-    // A reused path buffer.
-    path_buf: Vec<usize>,
+    // The number of generations run so far.
+    generation: usize,
+
+    // The number of generations since `best_path` last improved.
+    stagnation: usize,
+}
+
+/// Convergence statistics for a single generation, reported via [Colony::run_with_callback].
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct GenerationStats {
+    /// The index of the generation these stats describe (`0` for the first).
+    pub generation: usize,
+
+    /// The best path fitness found across all generations so far.
+    pub best_fitness: f32,
+
+    /// The mean path quality across all ants in this generation.
+    pub mean_quality: f32,
+
+    /// The highest path quality found by any ant in this generation.
+    pub max_quality: f32,
+
+    /// The number of distinct edges carrying a nonzero pheromone level after this generation.
+    pub pheromone_edges: usize,
+
+    /// The number of generations since `best_fitness` last improved.
+    pub stagnation: usize,
+}
+
+/// Constraints a completed path must satisfy: a fixed start node, an optional required terminal
+/// node, and a set of waypoints that must all appear somewhere in the path. Turns the solver from
+/// open TSP into a constrained routing engine.
+#[derive(Debug, Clone, Default)]
+pub struct Itinerary {
+    /// The node ants must start at, or `None` to let [Visitor::reset] pick randomly.
+    pub start: Option<usize>,
+
+    /// The node completed paths must end on, or `None` to accept any terminal node.
+    pub required_end: Option<usize>,
+
+    /// Nodes that must all appear somewhere in a completed path.
+    pub waypoints: Vec<usize>,
+}
+
+impl Itinerary {
+    /// Returns whether `path` satisfies this itinerary's `required_end` and `waypoints`.
+    pub fn is_satisfied_by(&self, path: &[usize]) -> bool {
+        if let Some(required_end) = self.required_end {
+            if path.last() != Some(&required_end) {
+                return false;
+            }
+        }
+
+        self.waypoints
+            .iter()
+            .all(|waypoint| path.contains(waypoint))
+    }
 }
 
 pub trait Visitor {
     type TargetIter: Iterator<Item = (f32, usize)>;
 
-    /// Resets the visitor's state, moves it to a random initial node, and returns that node.
-    fn reset(&mut self) -> usize;
+    /// Resets the visitor's state and moves it to `start` if given, otherwise to a random initial
+    /// node. Returns the node it starts at.
+    fn reset(&mut self, start: Option<usize>) -> usize;
 
     /// Enumerates the visitor's target nodes at a given index. This index should be consistent with
     /// the index the visitor is currently logically standing at.
@@ -34,13 +97,58 @@ pub trait Visitor {
 }
 
 pub trait Evaluator {
-    /// Computes the overall edge's quality based off its [Visitor]-reported quality and pheromone
-    /// level.
-    fn edge_quality(&self, quality: f32, pheromone: f32) -> f32;
-
-    /// Transforms the cumulative ([Visitor] reported; not [edge_quality]) quality of a given path
-    /// into a quantity of pheromones to be deposited.
+    /// Transforms the cumulative ([Visitor]-reported) quality of a given path into a quantity of
+    /// pheromones to be deposited.
     fn pheromones_deposited(&self, total_quality: f32) -> f32;
+
+    /// The exponent applied to pheromone level in the random-proportional transition rule.
+    /// Defaults to `1.0`.
+    fn alpha(&self) -> f32 {
+        1.0
+    }
+
+    /// The exponent applied to [Visitor]-reported heuristic quality in the random-proportional
+    /// transition rule. Defaults to `1.0`.
+    fn beta(&self) -> f32 {
+        1.0
+    }
+
+    /// The fraction of pheromone lost to evaporation at the start of each generation, in `[0,
+    /// 1)`. Defaults to `0.0` (no evaporation).
+    fn evaporation_rate(&self) -> f32 {
+        0.0
+    }
+
+    /// The maximum number of steps an ant may walk before its walk is forcibly terminated, as a
+    /// safety cap for [Visitor] graphs that permit revisiting nodes. Defaults to `usize::MAX`
+    /// (no cap beyond cycle detection).
+    fn max_steps(&self) -> usize {
+        usize::MAX
+    }
+
+    /// The Ant Colony System greedy factor, in `[0, 1]`. With probability `q0` an ant exploits by
+    /// picking the edge maximizing [Evaluator::edge_weight] outright; otherwise it explores via
+    /// the random-proportional roulette rule. `q0 = 0.0` (the default) is pure Ant System;
+    /// `q0 = 1.0` is pure greedy exploitation.
+    fn q0(&self) -> f32 {
+        0.0
+    }
+
+    /// Computes the random-proportional transition weight `pheromone^alpha * quality^beta` for a
+    /// candidate edge. An exponent of `0` takes the fast path of skipping its `powf`.
+    fn edge_weight(&self, quality: f32, pheromone: f32) -> f32 {
+        let alpha = self.alpha();
+        let beta = self.beta();
+
+        let pheromone_term = if alpha == 0. {
+            1.
+        } else {
+            pheromone.powf(alpha)
+        };
+        let quality_term = if beta == 0. { 1. } else { quality.powf(beta) };
+
+        pheromone_term * quality_term
+    }
 }
 
 impl Colony {
@@ -53,67 +161,188 @@ impl Colony {
     /// The [Visitor] provides a way to extract contextual information about which nodes a "traveller"
     /// can move to as well as their related quality.
     ///
-    /// The [Evaluator] provides a way to convert quality and pheromone levels into a single
-    /// `edge_quality` number specifying *deterministically* how good a given edge is as well as
-    /// convert a given overall path quality into the quantity of pheromones deposited at each
-    /// travelled edge.
-    pub fn run<V: Visitor, E: Evaluator>(
+    /// The [Evaluator] provides a way to convert quality and pheromone levels into the edge weight
+    /// used for random-proportional (or, with `q0` > 0, pseudo-random-proportional) selection, as
+    /// well as convert a given overall path quality into the quantity of pheromones deposited at
+    /// each travelled edge.
+    ///
+    /// Ants within a generation only read the frozen pheromone map and don't mutate shared state
+    /// during their walk, so the walk phase is run in parallel with rayon: `visitor` is cloned
+    /// once per ant and each ant is seeded its own RNG off of `rng`. Deposits and the best path
+    /// are then folded back in serially.
+    pub fn run<V, E>(
         &mut self,
         ant_count: usize,
-        visitor: &mut V,
+        visitor: &V,
         evaluator: &E,
-    ) {
-        // Copy previous map pheromone levels to current map
+        rng: &mut SmallRng,
+        itinerary: &Itinerary,
+    ) where
+        V: Visitor + Clone + Send + Sync,
+        E: Evaluator + Sync,
+    {
+        self.step_generation(ant_count, visitor, evaluator, rng, itinerary);
+    }
+
+    /// Like [Colony::run], but hands `callback` a [GenerationStats] snapshot once the generation
+    /// completes. This lets callers observe convergence (and decide to stop early, plot progress,
+    /// or retune `alpha`/`beta`/`rho`) without touching the core simulation loop.
+    pub fn run_with_callback<V, E, F>(
+        &mut self,
+        ant_count: usize,
+        visitor: &V,
+        evaluator: &E,
+        rng: &mut SmallRng,
+        itinerary: &Itinerary,
+        mut callback: F,
+    ) where
+        V: Visitor + Clone + Send + Sync,
+        E: Evaluator + Sync,
+        F: FnMut(&GenerationStats),
+    {
+        let stats = self.step_generation(ant_count, visitor, evaluator, rng, itinerary);
+        callback(&stats);
+    }
+
+    fn step_generation<V, E>(
+        &mut self,
+        ant_count: usize,
+        visitor: &V,
+        evaluator: &E,
+        rng: &mut SmallRng,
+        itinerary: &Itinerary,
+    ) -> GenerationStats
+    where
+        V: Visitor + Clone + Send + Sync,
+        E: Evaluator + Sync,
+    {
+        // Copy previous map pheromone levels to current map, evaporating as we go.
+        let rho = evaluator.evaporation_rate();
         for edge in &mut self.edges.values_mut() {
-            edge.copy_pheromones(self.use_map_b);
-        }
-
-        // Simulate ants
-        for _ in 0..ant_count {
-            // Clear ant's path buffer.
-            self.path_buf.clear();
-
-            // Store ant state.
-            let mut total_quality = 0.;
-            let mut curr_index = visitor.reset();
-
-            // Travel until we've reached a terminal point.
-            loop {
-                let choice = visitor
-                    .targets(curr_index)
-                    .map(|(quality, target_index)| {
-                        let pheromone = self.edges[&EdgeKey::new(curr_index, target_index)]
-                            .get_pheromone(self.use_map_b);
-
-                        let visit_quality = evaluator.edge_quality(quality, pheromone);
-
-                        (visit_quality, quality, target_index)
-                    })
-                    .max_by(|(visit_quality_a, _, _), (visit_quality_b, _, _)| {
-                        visit_quality_a.partial_cmp(visit_quality_b).unwrap()
-                    });
-
-                if let Some((_, quality, target_index)) = choice {
-                    // Accumulate the quality of the path.
-                    total_quality += quality;
-
-                    // Move to the target node.
-                    curr_index = target_index;
-                    visitor.walk_to(curr_index);
-                    self.path_buf.push(curr_index);
-                } else {
-                    // We're at a dead end. Our job is done.
-                    break;
+            edge.copy_pheromones(self.use_map_b, rho);
+        }
+
+        // Draw a per-ant seed up front so each ant's walk is independent and reproducible.
+        let seeds: Vec<u64> = (0..ant_count).map(|_| rng.gen()).collect();
+        let edges = &self.edges;
+        let use_map_b = self.use_map_b;
+
+        // Simulate ants in parallel. Each ant walks with its own cloned visitor, path buffer, and
+        // RNG, reading the shared (read-only, for the duration of the walk) pheromone map.
+        let walks: Vec<(f32, Vec<usize>, bool)> = seeds
+            .into_par_iter()
+            .map(|seed| {
+                let mut ant_visitor = visitor.clone();
+                let mut ant_rng = SmallRng::seed_from_u64(seed);
+                let mut path_buf = Vec::new();
+                let mut quality_buf: Vec<f32> = Vec::new();
+                let mut candidates = Vec::new();
+
+                let start_node = ant_visitor.reset(itinerary.start);
+                let mut curr_index = start_node;
+                let max_steps = evaluator.max_steps();
+                let q0 = evaluator.q0();
+
+                // Brent's cycle detection: `tortoise` is the reference node the hare is compared
+                // against; `power` is the (doubling) window the hare walks before the tortoise
+                // catches up, and `lambda` counts the hare's steps within the current window.
+                let mut tortoise = curr_index;
+                let mut power = 1usize;
+                let mut lambda = 0usize;
+                let mut steps = 0usize;
+
+                // Travel until we've reached a terminal point.
+                loop {
+                    // Build the candidate weights for the random-proportional transition rule.
+                    candidates.clear();
+                    candidates.extend(ant_visitor.targets(curr_index).map(
+                        |(quality, target_index)| {
+                            let pheromone = edges[&EdgeKey::new(curr_index, target_index)]
+                                .get_pheromone(use_map_b);
+
+                            let weight = evaluator.edge_weight(quality, pheromone);
+
+                            (weight, quality, target_index)
+                        },
+                    ));
+
+                    let choice = Self::choose_next(&candidates, &mut ant_rng, q0);
+
+                    if let Some((quality, target_index)) = choice {
+                        // Move to the target node.
+                        curr_index = target_index;
+                        ant_visitor.walk_to(curr_index);
+                        path_buf.push(curr_index);
+                        quality_buf.push(quality);
+                        steps += 1;
+
+                        if steps >= max_steps {
+                            // Safety cap: bail out rather than walk unbounded.
+                            break;
+                        }
+
+                        if curr_index == tortoise {
+                            // Phase 1 (above) only tells us *that* `curr_index` repeats an
+                            // earlier node; it doesn't tell us *which* one, so `lambda` can't be
+                            // turned into a truncation length on its own. Phase 2: since the full
+                            // walk is already kept in `path_buf`, find the exact first occurrence
+                            // (mu) of `curr_index` by scanning it directly, then keep the path up
+                            // to and including it and drop everything after — that's the looped
+                            // suffix that revisits it.
+                            let mu = std::iter::once(start_node)
+                                .chain(path_buf[..path_buf.len() - 1].iter().copied())
+                                .position(|node| node == curr_index)
+                                .expect("tortoise is always a previously visited node");
+
+                            path_buf.truncate(mu);
+                            quality_buf.truncate(mu);
+                            break;
+                        }
+
+                        lambda += 1;
+                        if lambda == power {
+                            tortoise = curr_index;
+                            power *= 2;
+                            lambda = 0;
+                        }
+                    } else {
+                        // We're at a dead end. Push the final node and our job is done.
+                        path_buf.push(curr_index);
+                        break;
+                    }
                 }
+
+                // Recompute from the (possibly truncated) retained steps so a cycle-truncated
+                // path's quality always matches the edges it's actually deposited along.
+                let total_quality: f32 = quality_buf.iter().sum();
+
+                // A path that skipped a required waypoint or didn't finish on the required
+                // terminal node is invalid: it must not reinforce pheromones or be considered
+                // for `best_path`.
+                let valid = itinerary.is_satisfied_by(&path_buf);
+
+                (total_quality, path_buf, valid)
+            })
+            .collect();
+
+        // Fold the ants' walks back in serially: deposit pheromones and track the best path.
+        let mut total_quality_sum = 0.;
+        let mut max_quality = 0.;
+        let mut improved = false;
+
+        for (total_quality, path, valid) in walks {
+            total_quality_sum += total_quality;
+            if total_quality > max_quality {
+                max_quality = total_quality;
             }
 
-            // Push the last node, completing the path.
-            self.path_buf.push(curr_index);
+            if !valid {
+                continue;
+            }
 
-            // Deposit pheromones
             let deposited = evaluator.pheromones_deposited(total_quality);
-            for i in 0..(self.path_buf.len() - 1) {
-                let key = EdgeKey::new(self.path_buf[i], self.path_buf[i + 1]);
+            for i in 0..(path.len() - 1) {
+                let key = EdgeKey::new(path[i], path[i + 1]);
                 let curr_level = self
                     .edges
                     .get_mut(&key)
@@ -125,12 +354,87 @@ impl Colony {
 
             // Update best path
             if total_quality > self.best_path.0 {
-                std::mem::swap(&mut self.best_path.1, &mut self.path_buf);
+                self.best_path = (total_quality, path);
+                improved = true;
             }
         }
 
+        self.stagnation = if improved { 0 } else { self.stagnation + 1 };
+
+        // Count before swapping trails, so this reads the buffer just evaporated and deposited
+        // into this generation rather than the (stale) one about to become active next
+        // generation.
+        let pheromone_edges = self
+            .edges
+            .values()
+            .filter(|edge| edge.get_pheromone(self.use_map_b) > 0.)
+            .count();
+
         // Swap pheromone trails
         self.use_map_b = !self.use_map_b;
+
+        let stats = GenerationStats {
+            generation: self.generation,
+            best_fitness: self.best_path.0,
+            mean_quality: if ant_count > 0 {
+                total_quality_sum / ant_count as f32
+            } else {
+                0.
+            },
+            max_quality,
+            pheromone_edges,
+            stagnation: self.stagnation,
+        };
+
+        self.generation += 1;
+
+        stats
+    }
+
+    /// Chooses the next edge to walk out of `candidates` using the Ant Colony System
+    /// pseudo-random-proportional rule: with probability `q0` it exploits by picking the edge of
+    /// highest weight outright, otherwise it falls back to random-proportional roulette-wheel
+    /// sampling. Falls back to a uniform choice when every candidate has zero weight, so ants
+    /// never deadlock on an all-zero-weight node. Returns `None` if there are no candidates at
+    /// all.
+    fn choose_next(
+        candidates: &[(f32, f32, usize)],
+        rng: &mut SmallRng,
+        q0: f32,
+    ) -> Option<(f32, usize)> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        if q0 > 0. && rng.gen::<f32>() < q0 {
+            let &(_, quality, target_index) = candidates
+                .iter()
+                .max_by(|(weight_a, _, _), (weight_b, _, _)| {
+                    weight_a.partial_cmp(weight_b).unwrap()
+                })
+                .unwrap();
+
+            return Some((quality, target_index));
+        }
+
+        let total_weight: f32 = candidates.iter().map(|(weight, _, _)| weight).sum();
+
+        if total_weight <= 0. {
+            let (_, quality, target_index) = candidates[rng.gen_range(0..candidates.len())];
+            return Some((quality, target_index));
+        }
+
+        let mut sample = rng.gen_range(0. ..total_weight);
+        for &(weight, quality, target_index) in candidates {
+            if sample < weight {
+                return Some((quality, target_index));
+            }
+            sample -= weight;
+        }
+
+        // Floating-point rounding may leave a residual sample; fall back to the last candidate.
+        let (_, quality, target_index) = *candidates.last().unwrap();
+        Some((quality, target_index))
     }
 
     /// Gets the best path ever discovered or `None` if no paths were ever explored.
@@ -142,9 +446,25 @@ impl Colony {
             None
         }
     }
+
+    /// Persists the colony's pheromone state (and best path found so far) to `path`, so training
+    /// can be checkpointed and resumed later.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let bytes = bincode::serialize(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        std::fs::write(path, bytes)
+    }
+
+    /// Loads a colony previously persisted with [Colony::save].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+
+        bincode::deserialize(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 struct EdgeKey(usize, usize);
 
 impl EdgeKey {
@@ -157,7 +477,7 @@ impl EdgeKey {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct Edge {
     pheromones: [f32; 2],
 }
@@ -171,13 +491,15 @@ impl Edge {
         &mut self.pheromones[use_b as usize]
     }
 
-    pub fn copy_pheromones(&mut self, into_b: bool) {
+    /// Copies pheromone from the inactive buffer into the active one (selected by `into_b`),
+    /// scaling it down by the evaporation rate `rho`.
+    pub fn copy_pheromones(&mut self, into_b: bool, rho: f32) {
         let [a, b] = &mut self.pheromones;
 
         if into_b {
-            *b = *a;
+            *b = *a * (1. - rho);
         } else {
-            *a = *b;
+            *a = *b * (1. - rho);
         }
     }
 }
@@ -196,7 +518,7 @@ impl Iterator for Dummy {
 impl Visitor for Dummy {
     type TargetIter = Dummy;
 
-    fn reset(&mut self) -> usize {
+    fn reset(&mut self, _start: Option<usize>) -> usize {
         unimplemented!()
     }
 
@@ -210,11 +532,470 @@ impl Visitor for Dummy {
 }
 
 impl Evaluator for Dummy {
-    fn edge_quality(&self, _: f32, _: f32) -> f32 {
+    fn pheromones_deposited(&self, _: f32) -> f32 {
         unimplemented!()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load_round_trips_pheromone_state() {
+        let mut colony = Colony::new();
+        colony.edges.insert(
+            EdgeKey::new(0, 1),
+            Edge {
+                pheromones: [0.25, 0.75],
+            },
+        );
+        colony.use_map_b = true;
+        colony.best_path = (4.5, vec![0, 1, 2]);
+
+        let path = std::env::temp_dir().join(format!(
+            "train-hopping-colony-test-{}-{}.bin",
+            std::process::id(),
+            "save_load_round_trips_pheromone_state"
+        ));
+
+        colony.save(&path).expect("save should succeed");
+        let loaded = Colony::load(&path).expect("load should succeed");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.use_map_b, colony.use_map_b);
+        assert_eq!(loaded.best_path, colony.best_path);
+        assert_eq!(
+            loaded
+                .best_path()
+                .map(|(fitness, path)| (fitness, path.to_vec())),
+            Some((4.5, vec![0, 1, 2]))
+        );
+    }
 
-    fn pheromones_deposited(&self, _: f32) -> f32 {
-        unimplemented!()
+    #[test]
+    fn save_load_round_trips_no_path_found_state() {
+        let colony = Colony::new();
+        assert_eq!(colony.best_path(), None);
+
+        let path = std::env::temp_dir().join(format!(
+            "train-hopping-colony-test-{}-{}.bin",
+            std::process::id(),
+            "save_load_round_trips_no_path_found_state"
+        ));
+
+        colony.save(&path).expect("save should succeed");
+        let loaded = Colony::load(&path).expect("load should succeed");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.best_path(), None);
+    }
+
+    #[test]
+    fn itinerary_with_no_constraints_accepts_any_path() {
+        let itinerary = Itinerary::default();
+        assert!(itinerary.is_satisfied_by(&[0, 1, 2]));
+        assert!(itinerary.is_satisfied_by(&[]));
+    }
+
+    #[test]
+    fn itinerary_rejects_paths_missing_a_waypoint() {
+        let itinerary = Itinerary {
+            waypoints: vec![1, 3],
+            ..Itinerary::default()
+        };
+
+        assert!(!itinerary.is_satisfied_by(&[0, 1, 2]));
+        assert!(itinerary.is_satisfied_by(&[0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn itinerary_rejects_paths_not_ending_on_the_required_terminal_node() {
+        let itinerary = Itinerary {
+            required_end: Some(2),
+            ..Itinerary::default()
+        };
+
+        assert!(!itinerary.is_satisfied_by(&[0, 1, 3]));
+        assert!(!itinerary.is_satisfied_by(&[]));
+        assert!(itinerary.is_satisfied_by(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn itinerary_requires_both_waypoints_and_terminal_node_together() {
+        let itinerary = Itinerary {
+            required_end: Some(2),
+            waypoints: vec![1],
+            ..Itinerary::default()
+        };
+
+        assert!(!itinerary.is_satisfied_by(&[0, 2]));
+        assert!(!itinerary.is_satisfied_by(&[0, 1, 3]));
+        assert!(itinerary.is_satisfied_by(&[0, 1, 2]));
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct WeightedEvaluator {
+        alpha: f32,
+        beta: f32,
+    }
+
+    impl Evaluator for WeightedEvaluator {
+        fn pheromones_deposited(&self, total_quality: f32) -> f32 {
+            total_quality
+        }
+
+        fn alpha(&self) -> f32 {
+            self.alpha
+        }
+
+        fn beta(&self) -> f32 {
+            self.beta
+        }
+    }
+
+    #[test]
+    fn edge_weight_computes_pheromone_alpha_times_quality_beta() {
+        let evaluator = WeightedEvaluator {
+            alpha: 2.0,
+            beta: 3.0,
+        };
+
+        assert_eq!(
+            evaluator.edge_weight(2.0, 5.0),
+            5.0f32.powf(2.0) * 2.0f32.powf(3.0)
+        );
+    }
+
+    #[test]
+    fn edge_weight_treats_a_zero_exponent_as_an_identity_factor() {
+        let evaluator = WeightedEvaluator {
+            alpha: 0.0,
+            beta: 0.0,
+        };
+
+        assert_eq!(evaluator.edge_weight(7.0, 9.0), 1.0);
+    }
+
+    #[test]
+    fn choose_next_returns_none_when_there_are_no_candidates() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        assert_eq!(Colony::choose_next(&[], &mut rng, 0.0), None);
+    }
+
+    #[test]
+    fn choose_next_falls_back_to_a_uniform_choice_when_every_weight_is_zero() {
+        let candidates = [(0.0, 2.5, 7)];
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        assert_eq!(
+            Colony::choose_next(&candidates, &mut rng, 0.0),
+            Some((2.5, 7))
+        );
+    }
+
+    #[test]
+    fn choose_next_skips_zero_weight_candidates_in_the_roulette_pass() {
+        // Only the middle candidate carries any weight, so the roulette sample must always
+        // land on it no matter what the RNG draws.
+        let candidates = [(0.0, 1.0, 1), (5.0, 2.0, 2), (0.0, 3.0, 3)];
+
+        for seed in 0..20 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            assert_eq!(
+                Colony::choose_next(&candidates, &mut rng, 0.0),
+                Some((2.0, 2))
+            );
+        }
+    }
+
+    #[test]
+    fn copy_pheromones_scales_down_the_source_buffer_by_the_evaporation_rate() {
+        let mut edge = Edge {
+            pheromones: [4.0, 0.0],
+        };
+
+        edge.copy_pheromones(true, 0.25);
+
+        assert_eq!(edge.pheromones, [4.0, 3.0]);
+    }
+
+    #[test]
+    fn copy_pheromones_leaves_pheromone_unchanged_with_zero_evaporation() {
+        let mut edge = Edge {
+            pheromones: [0.0, 6.0],
+        };
+
+        edge.copy_pheromones(false, 0.0);
+
+        assert_eq!(edge.pheromones, [6.0, 6.0]);
+    }
+
+    #[test]
+    fn copy_pheromones_fully_clears_the_destination_at_an_evaporation_rate_of_one() {
+        let mut edge = Edge {
+            pheromones: [2.0, 0.0],
+        };
+
+        edge.copy_pheromones(true, 1.0);
+
+        assert_eq!(edge.pheromones, [2.0, 0.0]);
+    }
+
+    /// A straight 0 -> 1 -> 2 graph with no branching, so every ant (regardless of RNG seed)
+    /// walks the identical path. Useful for isolating the serial fold's bookkeeping from the
+    /// transition rule.
+    #[derive(Debug, Clone, Default)]
+    struct LinearVisitor;
+
+    impl Visitor for LinearVisitor {
+        type TargetIter = std::vec::IntoIter<(f32, usize)>;
+
+        fn reset(&mut self, start: Option<usize>) -> usize {
+            start.unwrap_or(0)
+        }
+
+        fn targets(&self, index: usize) -> Self::TargetIter {
+            match index {
+                0 => vec![(2.0, 1)].into_iter(),
+                1 => vec![(3.0, 2)].into_iter(),
+                _ => vec![].into_iter(),
+            }
+        }
+
+        fn walk_to(&mut self, _index: usize) {}
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct IdentityEvaluator;
+
+    impl Evaluator for IdentityEvaluator {
+        fn pheromones_deposited(&self, total_quality: f32) -> f32 {
+            total_quality
+        }
+
+        // Cap the walk at exactly the 2 edges `LinearVisitor` offers, so it stops right after
+        // reaching the dead-end node 2 rather than looping back into the empty-candidate branch.
+        fn max_steps(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn run_deposits_pheromones_and_records_the_best_path_across_a_generation_of_ants() {
+        let mut colony = Colony::new();
+        colony.edges.insert(
+            EdgeKey::new(0, 1),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+        colony.edges.insert(
+            EdgeKey::new(1, 2),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+
+        let mut rng = SmallRng::seed_from_u64(3);
+        colony.run(
+            3,
+            &LinearVisitor,
+            &IdentityEvaluator,
+            &mut rng,
+            &Itinerary::default(),
+        );
+
+        // All 3 ants walk the identical [1, 2] path (there's only one candidate at each node),
+        // each depositing its full 5.0 (= 2.0 + 3.0) quality along the edge between its two
+        // recorded nodes, so the active buffer should hold the sum of all of them. The start
+        // node itself isn't part of the recorded path, so no deposit lands on edge (0, 1).
+        assert_eq!(colony.edges[&EdgeKey::new(0, 1)].pheromones[0], 0.0);
+        assert_eq!(colony.edges[&EdgeKey::new(1, 2)].pheromones[0], 15.0);
+
+        let (fitness, path) = colony
+            .best_path()
+            .expect("a valid path should have been recorded");
+        assert_eq!(fitness, 5.0);
+        assert_eq!(path, &[1, 2]);
+    }
+
+    #[test]
+    fn run_excludes_itinerary_violating_walks_from_deposits_and_best_path() {
+        let mut colony = Colony::new();
+        colony.edges.insert(
+            EdgeKey::new(0, 1),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+        colony.edges.insert(
+            EdgeKey::new(1, 2),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+
+        let itinerary = Itinerary {
+            required_end: Some(99),
+            ..Itinerary::default()
+        };
+
+        let mut rng = SmallRng::seed_from_u64(3);
+        colony.run(3, &LinearVisitor, &IdentityEvaluator, &mut rng, &itinerary);
+
+        assert_eq!(colony.edges[&EdgeKey::new(0, 1)].pheromones[0], 0.0);
+        assert_eq!(colony.edges[&EdgeKey::new(1, 2)].pheromones[0], 0.0);
+        assert_eq!(colony.best_path(), None);
+    }
+
+    #[test]
+    fn run_with_callback_reports_accurate_convergence_statistics() {
+        let mut colony = Colony::new();
+        colony.edges.insert(
+            EdgeKey::new(0, 1),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+        colony.edges.insert(
+            EdgeKey::new(1, 2),
+            Edge {
+                pheromones: [0.0, 0.0],
+            },
+        );
+
+        let mut rng = SmallRng::seed_from_u64(11);
+        let mut snapshots = Vec::new();
+        for _ in 0..2 {
+            colony.run_with_callback(
+                2,
+                &LinearVisitor,
+                &IdentityEvaluator,
+                &mut rng,
+                &Itinerary::default(),
+                |stats| snapshots.push(*stats),
+            );
+        }
+
+        assert_eq!(snapshots[0].generation, 0);
+        assert_eq!(snapshots[0].best_fitness, 5.0);
+        assert_eq!(snapshots[0].mean_quality, 5.0);
+        assert_eq!(snapshots[0].max_quality, 5.0);
+        assert_eq!(snapshots[0].pheromone_edges, 1);
+        assert_eq!(snapshots[0].stagnation, 0);
+
+        // The second generation walks the identical path and ties (rather than beats) the
+        // existing best, so it should count as a stagnant generation even though pheromones
+        // keep accumulating.
+        assert_eq!(snapshots[1].generation, 1);
+        assert_eq!(snapshots[1].best_fitness, 5.0);
+        assert_eq!(snapshots[1].mean_quality, 5.0);
+        assert_eq!(snapshots[1].pheromone_edges, 1);
+        assert_eq!(snapshots[1].stagnation, 1);
+    }
+
+    #[test]
+    fn choose_next_exploits_the_highest_weight_candidate_when_q0_is_one() {
+        let candidates = [(1.0, 10.0, 1), (9.0, 20.0, 2), (3.0, 30.0, 3)];
+
+        for seed in 0..20 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            assert_eq!(
+                Colony::choose_next(&candidates, &mut rng, 1.0),
+                Some((20.0, 2))
+            );
+        }
+    }
+
+    #[test]
+    fn choose_next_never_exploits_when_q0_is_zero() {
+        // With `q0 = 0.0` the exploit branch must never run, so the roulette pass always
+        // decides; since only the middle candidate carries any weight, it's always the one
+        // chosen, regardless of what the RNG draws.
+        let candidates = [(0.0, 1.0, 1), (5.0, 2.0, 2), (0.0, 3.0, 3)];
+
+        for seed in 0..20 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            assert_eq!(
+                Colony::choose_next(&candidates, &mut rng, 0.0),
+                Some((2.0, 2))
+            );
+        }
+    }
+
+    /// A "lollipop" graph: a single straight run from node 0 into node 1,
+    /// then a 1 -> 2 -> 3 -> 1 cycle that repeats forever. Every node has
+    /// exactly one outgoing edge, so the walk is fully deterministic
+    /// regardless of RNG draws, which makes it a reliable way to exercise
+    /// the cycle-detection/truncation logic in isolation.
+    #[derive(Debug, Clone, Default)]
+    struct CyclicVisitor;
+
+    impl Visitor for CyclicVisitor {
+        type TargetIter = std::iter::Once<(f32, usize)>;
+
+        fn reset(&mut self, start: Option<usize>) -> usize {
+            start.unwrap_or(0)
+        }
+
+        fn targets(&self, index: usize) -> Self::TargetIter {
+            let next = match index {
+                0 => 1,
+                1 => 2,
+                2 => 3,
+                _ => 1,
+            };
+            std::iter::once((1.0, next))
+        }
+
+        fn walk_to(&mut self, _index: usize) {}
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct CyclicEvaluator;
+
+    impl Evaluator for CyclicEvaluator {
+        fn pheromones_deposited(&self, total_quality: f32) -> f32 {
+            total_quality
+        }
+
+        fn max_steps(&self) -> usize {
+            50
+        }
+    }
+
+    #[test]
+    fn run_truncates_the_looped_suffix_once_a_cycle_is_detected() {
+        let mut colony = Colony::new();
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (1, 3)] {
+            colony.edges.insert(
+                EdgeKey::new(a, b),
+                Edge {
+                    pheromones: [1.0, 1.0],
+                },
+            );
+        }
+
+        let mut rng = SmallRng::seed_from_u64(7);
+        colony.run(
+            1,
+            &CyclicVisitor,
+            &CyclicEvaluator,
+            &mut rng,
+            &Itinerary::default(),
+        );
+
+        let (fitness, path) = colony
+            .best_path()
+            .expect("the lead-in up to the first repeated node should be recorded");
+
+        // The walk is 0 -> 1 -> 2 -> 3 -> 1 -> 2 -> 3; Brent's check first
+        // fires on the second visit to node 3 (the tortoise by then), so
+        // everything after node 3's first visit (the looped suffix) must be
+        // dropped, not just the last node, and nothing before it should be
+        // lost either.
+        assert_eq!(path, &[1, 2, 3]);
+        assert_eq!(fitness, 3.0);
     }
 }